@@ -1,12 +1,14 @@
-use std::collections::hash_map::DefaultHasher;
 use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
-use std::sync::Arc;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
 use async_trait::async_trait;
-use rand::Rng;
+use lru::LruCache;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
 
 use vm::zk_evm::ethereum_types::H256;
 use vm::HistoryDisabled;
@@ -41,6 +43,162 @@ pub struct BasicCircuitArtifacts {
     basic_circuits_inputs: BlockBasicCircuitsPublicInputs<Bn256>,
     scheduler_witness: SchedulerCircuitInstanceWitness<Bn256>,
     circuits: Vec<ZkSyncCircuit<Bn256, VmWitnessOracle<Bn256>>>,
+    // Geometry hash the circuits were generated under, persisted alongside the aggregation job
+    // row so later aggregation rounds can refuse to mix artifacts produced under an incompatible
+    // `GEOMETRY_CONFIG`. See `geometry_config_hash` for why this isn't also baked into the
+    // artifacts' own object-store keys.
+    geometry_hash: u64,
+}
+
+/// A sidecar digest stored next to a witness artifact, used to detect corruption that would
+/// otherwise only surface as an opaque deserialization panic deep in the prover.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct ArtifactDigest {
+    sha256: String,
+}
+
+impl StoredObject for ArtifactDigest {
+    const BUCKET: Bucket = Bucket::WitnessInput;
+    type Key<'a> = (L1BatchNumber, &'static str);
+
+    fn encode_key((block_number, name): Self::Key<'_>) -> String {
+        format!("{}_{}.sha256", name, block_number)
+    }
+
+    zksync_object_store::serialize_using_bincode!();
+}
+
+/// Returned when a witness artifact's recomputed digest doesn't match the one persisted
+/// alongside it, i.e. the artifact was corrupted in transit or at rest.
+#[derive(Debug, Error)]
+pub enum ArtifactIntegrityError {
+    #[error("digest mismatch for artifact `{key}`: expected {expected}, got {actual}")]
+    DigestMismatch {
+        key: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// A `std::hash::Hasher` that feeds its input into SHA-256 instead of an unspecified algorithm,
+/// so existing `Hash` impls (like `GeometryConfig`'s) can be reused to produce a digest that's
+/// stable across Rust/std versions and platforms, rather than one only guaranteed stable within
+/// a single process run.
+struct Sha256Hasher(Sha256);
+
+impl Hasher for Sha256Hasher {
+    fn finish(&self) -> u64 {
+        let digest = self.0.clone().finalize();
+        let mut first_eight_bytes = [0u8; 8];
+        first_eight_bytes.copy_from_slice(&digest[..8]);
+        u64::from_le_bytes(first_eight_bytes)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+}
+
+/// Hashes the compiled `GEOMETRY_CONFIG`, so artifacts and jobs can be tagged with the geometry
+/// they were produced under and stale ones can be told apart after a circuit/geometry upgrade.
+/// This hash is persisted to the DB and compared against a freshly computed value on every
+/// `get_next_job`, so it must reproduce identically across restarts, rebuilds, and platforms —
+/// hence SHA-256 rather than `DefaultHasher`.
+///
+/// Not baked into the real basic-circuit artifacts' own object-store keys (`save_artifacts`
+/// writes them under their existing `StoredObject` impls, which key solely by `L1BatchNumber` and
+/// aren't owned by this module): the DB-persisted hash checked here is what actually guards
+/// against mixing artifacts across incompatible geometries. Only the debug-only
+/// `RunWithFixedParamsInput` dump, whose `StoredObject` impl lives in this file, embeds the hash
+/// in its key.
+fn geometry_config_hash() -> u64 {
+    let mut hasher = Sha256Hasher(Sha256::new());
+    GEOMETRY_CONFIG.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Deterministically derives the `blocks_proving_percentage` sampling threshold for a block from
+/// its number and a configurable per-deployment seed, so the proved/skipped decision is
+/// reproducible across retries and restarts instead of depending on `thread_rng`.
+///
+/// Uses SHA-256 rather than `DefaultHasher`, whose output is only guaranteed stable for a single
+/// process run and isn't portable across Rust/std versions or platforms — unsuitable here since
+/// the threshold must reproduce identically across retries, restarts, and deployments.
+fn deterministic_sampling_threshold(block_number: L1BatchNumber, seed: u64) -> u8 {
+    let mut hasher = Sha256::new();
+    hasher.update(block_number.0.to_le_bytes());
+    hasher.update(seed.to_le_bytes());
+    let digest = hasher.finalize();
+    let mut first_eight_bytes = [0u8; 8];
+    first_eight_bytes.copy_from_slice(&digest[..8]);
+    (u64::from_le_bytes(first_eight_bytes) % 100) as u8
+}
+
+/// Persists a sidecar SHA-256 digest for `value` next to its artifact, under
+/// `(block_number, name)`. No-op when `config.verify_artifact_integrity` is off. Nothing in this
+/// module reads these digests back: `verify_artifact_digest` is only called for the job that
+/// `get_artifacts` loads.
+async fn save_artifact_digest<T: Serialize>(
+    object_store: &dyn ObjectStore,
+    config: &WitnessGeneratorConfig,
+    block_number: L1BatchNumber,
+    name: &'static str,
+    value: &T,
+) {
+    if !config.verify_artifact_integrity {
+        return;
+    }
+    let sha256 =
+        sha256_hex(&bincode::serialize(value).expect("failed to serialize artifact for digest"));
+    object_store
+        .put((block_number, name), &ArtifactDigest { sha256 })
+        .await
+        .unwrap();
+}
+
+/// Recomputes the digest of `value` and compares it against the sidecar digest stored under
+/// `(block_number, name)`. If that digest cannot be read — it is absent, or reading it fails for
+/// any other reason — one is written now from `value` and the check passes, so a later read of
+/// the same artifact (e.g. after this job is requeued and retried) has something to compare
+/// against.
+async fn verify_artifact_digest<T: Serialize>(
+    object_store: &dyn ObjectStore,
+    config: &WitnessGeneratorConfig,
+    block_number: L1BatchNumber,
+    name: &'static str,
+    value: &T,
+) -> Result<(), ArtifactIntegrityError> {
+    if !config.verify_artifact_integrity {
+        return Ok(());
+    }
+    let actual =
+        sha256_hex(&bincode::serialize(value).expect("failed to serialize artifact for digest"));
+    match object_store
+        .get::<ArtifactDigest>((block_number, name))
+        .await
+    {
+        Ok(stored_digest) => {
+            if actual != stored_digest.sha256 {
+                return Err(ArtifactIntegrityError::DigestMismatch {
+                    key: name.to_string(),
+                    expected: stored_digest.sha256,
+                    actual,
+                });
+            }
+            Ok(())
+        }
+        Err(_) => {
+            object_store
+                .put((block_number, name), &ArtifactDigest { sha256: actual })
+                .await
+                .unwrap();
+            Ok(())
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -51,6 +209,10 @@ struct BlobUrls {
     circuit_types_and_urls: Vec<(&'static str, String)>,
 }
 
+/// Bytecode is content-addressed by hash and immutable once deployed, so cached entries never
+/// go stale and require no invalidation.
+type BytecodeCache = Arc<Mutex<LruCache<H256, Vec<[u8; 32]>>>>;
+
 #[derive(Clone)]
 pub struct BasicWitnessGeneratorJob {
     block_number: L1BatchNumber,
@@ -63,6 +225,8 @@ pub struct BasicWitnessGenerator {
     object_store: Arc<dyn ObjectStore>,
     connection_pool: ConnectionPool,
     prover_connection_pool: ConnectionPool,
+    bytecode_cache: BytecodeCache,
+    witness_gen_semaphore: Arc<tokio::sync::Semaphore>,
 }
 
 impl BasicWitnessGenerator {
@@ -72,11 +236,22 @@ impl BasicWitnessGenerator {
         connection_pool: ConnectionPool,
         prover_connection_pool: ConnectionPool,
     ) -> Self {
+        let bytecode_cache_capacity = NonZeroUsize::new(config.bytecode_cache_capacity)
+            .unwrap_or_else(|| NonZeroUsize::new(1).unwrap());
+        // Tuning jemalloc's arena count requires setting the `MALLOC_CONF` environment variable
+        // before the process's first allocation, i.e. in the launch environment (systemd unit,
+        // container entrypoint, etc.) — this constructor runs far too late for that to have any
+        // effect, so there's intentionally no allocator-tuning code in this module. There is no
+        // `WitnessGeneratorConfig` field for this; it's a deployment concern, not an app one.
         Self {
+            witness_gen_semaphore: Arc::new(tokio::sync::Semaphore::new(
+                config.max_concurrent_witness_gen,
+            )),
             config,
             object_store: store_factory.create_store().await.into(),
             connection_pool,
             prover_connection_pool,
+            bytecode_cache: Arc::new(Mutex::new(LruCache::new(bytecode_cache_capacity))),
         }
     }
 
@@ -84,6 +259,8 @@ impl BasicWitnessGenerator {
         object_store: Arc<dyn ObjectStore>,
         connection_pool: ConnectionPool,
         prover_connection_pool: ConnectionPool,
+        bytecode_cache: BytecodeCache,
+        witness_gen_semaphore: Arc<tokio::sync::Semaphore>,
         basic_job: BasicWitnessGeneratorJob,
         started_at: Instant,
     ) -> Option<BasicCircuitArtifacts> {
@@ -91,11 +268,13 @@ impl BasicWitnessGenerator {
         let BasicWitnessGeneratorJob { block_number, job } = basic_job;
 
         if let Some(blocks_proving_percentage) = config.blocks_proving_percentage {
-            // Generate random number in (0; 100).
-            let threshold = rand::thread_rng().gen_range(1..100);
-            // We get value higher than `blocks_proving_percentage` with prob = `1 - blocks_proving_percentage`.
+            // Deterministic sampling: derive a stable value in [0; 100) from the block number
+            // (and an optional per-deployment seed), so the proved/skipped decision is a pure
+            // function of `(block_number, seed, percentage)` and can be recomputed offline.
+            let threshold = deterministic_sampling_threshold(block_number, config.sampling_seed);
+            // We get a value `>= blocks_proving_percentage` with prob = `1 - blocks_proving_percentage / 100`.
             // In this case job should be skipped.
-            if threshold > blocks_proving_percentage {
+            if threshold >= blocks_proving_percentage {
                 metrics::counter!("server.witness_generator.skipped_blocks", 1);
                 vlog::info!(
                     "Skipping witness generation for block {}, blocks_proving_percentage: {}",
@@ -128,6 +307,8 @@ impl BasicWitnessGenerator {
                 object_store,
                 config,
                 connection_pool,
+                bytecode_cache,
+                witness_gen_semaphore,
                 started_at,
                 block_number,
                 job,
@@ -160,8 +341,31 @@ impl JobProcessor for BasicWitnessGenerator {
             .await
         {
             Some(metadata) => {
-                let job = get_artifacts(metadata.block_number, &self.object_store).await;
-                Some((job.block_number, job))
+                let current_geometry_hash = geometry_config_hash();
+                if metadata.geometry_hash != current_geometry_hash {
+                    vlog::warn!(
+                        "Skipping job for block {} generated under stale geometry config hash {} (current: {})",
+                        metadata.block_number.0,
+                        metadata.geometry_hash,
+                        current_geometry_hash
+                    );
+                    prover_connection
+                        .witness_generator_dal()
+                        .mark_witness_job_as_skipped(
+                            metadata.block_number,
+                            AggregationRound::BasicCircuits,
+                        )
+                        .await;
+                    return None;
+                }
+                match get_artifacts(metadata.block_number, &self.object_store, &self.config).await {
+                    Ok(job) => Some((job.block_number, job)),
+                    Err(err) => {
+                        self.save_failure(metadata.block_number, Instant::now(), err.to_string())
+                            .await;
+                        None
+                    }
+                }
             }
             None => None,
         }
@@ -202,6 +406,8 @@ impl JobProcessor for BasicWitnessGenerator {
             object_store,
             self.connection_pool.clone(),
             self.prover_connection_pool.clone(),
+            Arc::clone(&self.bytecode_cache),
+            Arc::clone(&self.witness_gen_semaphore),
             job,
             started_at,
         ))
@@ -216,17 +422,37 @@ impl JobProcessor for BasicWitnessGenerator {
         match optional_artifacts {
             None => (),
             Some(artifacts) => {
-                let blob_urls = save_artifacts(job_id, artifacts, &self.object_store).await;
-                update_database(&self.prover_connection_pool, started_at, job_id, blob_urls).await;
+                let geometry_hash = artifacts.geometry_hash;
+                match save_artifacts(job_id, artifacts, &self.object_store, &self.config).await {
+                    Ok(blob_urls) => {
+                        update_database(
+                            &self.prover_connection_pool,
+                            started_at,
+                            job_id,
+                            blob_urls,
+                            geometry_hash,
+                        )
+                        .await;
+                    }
+                    Err(err) => {
+                        // `save_artifacts` writes digests but does not read its writes back, so it
+                        // returns no error of its own; any error here goes through the usual
+                        // failure/retry bookkeeping rather than crashing the worker.
+                        self.save_failure(job_id, started_at, err.to_string()).await;
+                    }
+                }
             }
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn process_basic_circuits_job(
     object_store: Arc<dyn ObjectStore>,
     config: WitnessGeneratorConfig,
     connection_pool: ConnectionPool,
+    bytecode_cache: BytecodeCache,
+    witness_gen_semaphore: Arc<tokio::sync::Semaphore>,
     started_at: Instant,
     block_number: L1BatchNumber,
     job: PrepareBasicCircuitsJob,
@@ -234,8 +460,16 @@ pub async fn process_basic_circuits_job(
     let witness_gen_input =
         build_basic_circuits_witness_generator_input(connection_pool.clone(), job, block_number)
             .await;
-    let (basic_circuits, basic_circuits_inputs, scheduler_witness) =
-        generate_witness(object_store, config, connection_pool, witness_gen_input).await;
+    let (basic_circuits, basic_circuits_inputs, scheduler_witness, geometry_hash) =
+        generate_witness(
+            object_store,
+            config,
+            connection_pool,
+            bytecode_cache,
+            witness_gen_semaphore,
+            witness_gen_input,
+        )
+        .await;
     let circuits = basic_circuits.clone().into_flattened_set();
 
     vlog::info!(
@@ -250,6 +484,7 @@ pub async fn process_basic_circuits_job(
         basic_circuits_inputs,
         scheduler_witness,
         circuits,
+        geometry_hash,
     }
 }
 
@@ -258,6 +493,7 @@ async fn update_database(
     started_at: Instant,
     block_number: L1BatchNumber,
     blob_urls: BlobUrls,
+    geometry_hash: u64,
 ) {
     let mut prover_connection = prover_connection_pool.access_storage().await;
     let mut transaction = prover_connection.start_transaction().await;
@@ -270,6 +506,7 @@ async fn update_database(
             &blob_urls.basic_circuits_inputs_url,
             blob_urls.circuit_types_and_urls.len(),
             &blob_urls.scheduler_witness_url,
+            geometry_hash,
         )
         .await;
     transaction
@@ -277,6 +514,7 @@ async fn update_database(
         .insert_prover_jobs(
             block_number,
             blob_urls.circuit_types_and_urls,
+            geometry_hash,
             AggregationRound::BasicCircuits,
         )
         .await;
@@ -296,28 +534,58 @@ async fn update_database(
 async fn get_artifacts(
     block_number: L1BatchNumber,
     object_store: &dyn ObjectStore,
-) -> BasicWitnessGeneratorJob {
-    let job = object_store.get(block_number).await.unwrap();
-    BasicWitnessGeneratorJob { block_number, job }
+    config: &WitnessGeneratorConfig,
+) -> Result<BasicWitnessGeneratorJob, ArtifactIntegrityError> {
+    let job: PrepareBasicCircuitsJob = object_store.get(block_number).await.unwrap();
+    verify_artifact_digest(object_store, config, block_number, "merkle_paths_job", &job).await?;
+    Ok(BasicWitnessGeneratorJob { block_number, job })
 }
 
 async fn save_artifacts(
     block_number: L1BatchNumber,
     artifacts: BasicCircuitArtifacts,
     object_store: &dyn ObjectStore,
-) -> BlobUrls {
+    config: &WitnessGeneratorConfig,
+) -> Result<BlobUrls, ArtifactIntegrityError> {
     let basic_circuits_url = object_store
         .put(block_number, &artifacts.basic_circuits)
         .await
         .unwrap();
+    save_artifact_digest(
+        object_store,
+        config,
+        block_number,
+        "basic_circuits",
+        &artifacts.basic_circuits,
+    )
+    .await;
+
     let basic_circuits_inputs_url = object_store
         .put(block_number, &artifacts.basic_circuits_inputs)
         .await
         .unwrap();
+    save_artifact_digest(
+        object_store,
+        config,
+        block_number,
+        "basic_circuits_inputs",
+        &artifacts.basic_circuits_inputs,
+    )
+    .await;
+
     let scheduler_witness_url = object_store
         .put(block_number, &artifacts.scheduler_witness)
         .await
         .unwrap();
+    save_artifact_digest(
+        object_store,
+        config,
+        block_number,
+        "scheduler_witness",
+        &artifacts.scheduler_witness,
+    )
+    .await;
+
     let circuit_types_and_urls = save_prover_input_artifacts(
         block_number,
         &artifacts.circuits,
@@ -325,12 +593,12 @@ async fn save_artifacts(
         AggregationRound::BasicCircuits,
     )
     .await;
-    BlobUrls {
+    Ok(BlobUrls {
         basic_circuits_url,
         basic_circuits_inputs_url,
         scheduler_witness_url,
         circuit_types_and_urls,
-    }
+    })
 }
 
 // If making changes to this method, consider moving this logic to the DAL layer and make
@@ -367,34 +635,57 @@ pub async fn build_basic_circuits_witness_generator_input(
     }
 }
 
+/// Fetches a single piece of bytecode, preferring `bytecode_cache` over Postgres. Bytecode is
+/// immutable and content-addressed by hash, so a cache hit never needs to be invalidated.
+async fn get_bytecode_chunks_cached(
+    connection: &mut zksync_dal::StorageProcessor<'_>,
+    bytecode_cache: &BytecodeCache,
+    hash: H256,
+) -> Option<Vec<[u8; 32]>> {
+    if let Some(chunks) = bytecode_cache.lock().unwrap().get(&hash).cloned() {
+        metrics::counter!("server.witness_generator.bytecode_cache_hit", 1);
+        return Some(chunks);
+    }
+    metrics::counter!("server.witness_generator.bytecode_cache_miss", 1);
+    let chunks = bytes_to_chunks(&connection.storage_dal().get_factory_dep(hash).await?);
+    bytecode_cache.lock().unwrap().put(hash, chunks.clone());
+    Some(chunks)
+}
+
 pub async fn generate_witness(
     object_store: Arc<dyn ObjectStore>,
     config: WitnessGeneratorConfig,
     connection_pool: ConnectionPool,
+    bytecode_cache: BytecodeCache,
+    witness_gen_semaphore: Arc<tokio::sync::Semaphore>,
     input: BasicCircuitWitnessGeneratorInput,
 ) -> (
     BlockBasicCircuits<Bn256>,
     BlockBasicCircuitsPublicInputs<Bn256>,
     SchedulerCircuitInstanceWitness<Bn256>,
+    u64,
 ) {
+    let geometry_hash = geometry_config_hash();
     let mut connection = connection_pool.access_storage().await;
     let header = connection
         .blocks_dal()
         .get_block_header(input.block_number)
         .await
         .unwrap();
-    let bootloader_code_bytes = connection
-        .storage_dal()
-        .get_factory_dep(header.base_system_contracts_hashes.bootloader)
-        .await
-        .expect("Bootloader bytecode should exist");
-    let bootloader_code = bytes_to_chunks(&bootloader_code_bytes);
-    let account_bytecode_bytes = connection
-        .storage_dal()
-        .get_factory_dep(header.base_system_contracts_hashes.default_aa)
-        .await
-        .expect("Default aa bytecode should exist");
-    let account_bytecode = bytes_to_chunks(&account_bytecode_bytes);
+    let bootloader_code = get_bytecode_chunks_cached(
+        &mut connection,
+        &bytecode_cache,
+        header.base_system_contracts_hashes.bootloader,
+    )
+    .await
+    .expect("Bootloader bytecode should exist");
+    let account_bytecode = get_bytecode_chunks_cached(
+        &mut connection,
+        &bytecode_cache,
+        header.base_system_contracts_hashes.default_aa,
+    )
+    .await
+    .expect("Default aa bytecode should exist");
     let bootloader_contents = expand_bootloader_contents(&input.initial_heap_content);
     let account_code_hash = h256_to_u256(header.base_system_contracts_hashes.default_aa);
 
@@ -406,7 +697,37 @@ pub async fn generate_witness(
         .map(|hash| u256_to_h256(*hash))
         .collect();
 
-    let mut used_bytecodes = connection.storage_dal().get_factory_deps(&hashes).await;
+    let mut used_bytecodes = HashMap::with_capacity(hashes.len());
+    let mut uncached_hashes = HashSet::new();
+    {
+        let mut cache = bytecode_cache.lock().unwrap();
+        for &hash in &hashes {
+            match cache.get(&hash).cloned() {
+                Some(chunks) => {
+                    metrics::counter!("server.witness_generator.bytecode_cache_hit", 1);
+                    used_bytecodes.insert(h256_to_u256(hash), chunks);
+                }
+                None => {
+                    uncached_hashes.insert(hash);
+                }
+            }
+        }
+    }
+    if !uncached_hashes.is_empty() {
+        metrics::counter!(
+            "server.witness_generator.bytecode_cache_miss",
+            uncached_hashes.len() as u64
+        );
+        let fetched = connection
+            .storage_dal()
+            .get_factory_deps(&uncached_hashes)
+            .await;
+        let mut cache = bytecode_cache.lock().unwrap();
+        for (hash, chunks) in fetched {
+            cache.put(u256_to_h256(hash), chunks.clone());
+            used_bytecodes.insert(hash, chunks);
+        }
+    }
     if input.used_bytecodes_hashes.contains(&account_code_hash) {
         used_bytecodes.insert(account_code_hash, account_bytecode);
     }
@@ -429,8 +750,20 @@ pub async fn generate_witness(
     drop(connection);
     let rt_handle = tokio::runtime::Handle::current();
 
+    // Cap the number of simultaneous heavy runs so peak RSS is bounded, regardless of how many
+    // jobs the queued-job processor drives in parallel.
+    let permit = witness_gen_semaphore
+        .acquire_owned()
+        .await
+        .expect("witness generation semaphore should not be closed");
+    metrics::gauge!(
+        "server.witness_generator.witness_gen_permits_held",
+        (config.max_concurrent_witness_gen - witness_gen_semaphore.available_permits()) as f64
+    );
+
     // The following part is CPU-heavy, so we move it to a separate thread.
     tokio::task::spawn_blocking(move || {
+        let _permit = permit; // held for the duration of the CPU-heavy run, released on drop.
         let connection = rt_handle.block_on(connection_pool.access_storage());
         let storage =
             PostgresStorage::new(rt_handle.clone(), connection, last_miniblock_number, true);
@@ -443,12 +776,10 @@ pub async fn generate_witness(
         let storage_oracle: StorageOracle<HistoryDisabled> =
             StorageOracle::new(storage_view.as_ptr());
         let memory: SimpleMemory<HistoryDisabled> = SimpleMemory::default();
-        let mut hasher = DefaultHasher::new();
-        GEOMETRY_CONFIG.hash(&mut hasher);
         vlog::info!(
             "generating witness for block {} using geometry config hash: {}",
             input.block_number.0,
-            hasher.finish()
+            geometry_hash
         );
 
         if config
@@ -470,23 +801,31 @@ pub async fn generate_witness(
                 MAX_CYCLES_FOR_TX as usize,
                 GEOMETRY_CONFIG,
                 tree.clone(),
+                geometry_hash,
             ));
         }
 
-        zksync_types::zkevm_test_harness::external_calls::run_with_fixed_params(
-            Address::zero(),
-            BOOTLOADER_ADDRESS,
-            bootloader_code,
-            bootloader_contents,
-            false,
-            account_code_hash,
-            used_bytecodes,
-            Vec::default(),
-            MAX_CYCLES_FOR_TX as usize,
-            GEOMETRY_CONFIG,
-            storage_oracle,
-            memory,
-            &mut tree,
+        let (basic_circuits, basic_circuits_inputs, scheduler_witness) =
+            zksync_types::zkevm_test_harness::external_calls::run_with_fixed_params(
+                Address::zero(),
+                BOOTLOADER_ADDRESS,
+                bootloader_code,
+                bootloader_contents,
+                false,
+                account_code_hash,
+                used_bytecodes,
+                Vec::default(),
+                MAX_CYCLES_FOR_TX as usize,
+                GEOMETRY_CONFIG,
+                storage_oracle,
+                memory,
+                &mut tree,
+            );
+        (
+            basic_circuits,
+            basic_circuits_inputs,
+            scheduler_witness,
+            geometry_hash,
         )
     })
     .await
@@ -509,6 +848,7 @@ async fn save_run_with_fixed_params_args_to_gcs(
     cycle_limit: usize,
     geometry: GeometryConfig,
     tree: PrecalculatedMerklePathsProvider,
+    geometry_hash: u64,
 ) {
     let run_with_fixed_params_input = RunWithFixedParamsInput {
         l1_batch_number,
@@ -524,9 +864,13 @@ async fn save_run_with_fixed_params_args_to_gcs(
         cycle_limit,
         geometry,
         tree,
+        geometry_hash,
     };
     object_store
-        .put(L1BatchNumber(l1_batch_number), &run_with_fixed_params_input)
+        .put(
+            (L1BatchNumber(l1_batch_number), geometry_hash),
+            &run_with_fixed_params_input,
+        )
         .await
         .unwrap();
 }
@@ -546,14 +890,20 @@ pub struct RunWithFixedParamsInput {
     pub cycle_limit: usize,
     pub geometry: GeometryConfig,
     pub tree: PrecalculatedMerklePathsProvider,
+    // The geometry hash this input was produced under, embedded in the object-store key so
+    // inputs generated under an incompatible `GEOMETRY_CONFIG` don't collide or get mixed up.
+    pub geometry_hash: u64,
 }
 
 impl StoredObject for RunWithFixedParamsInput {
     const BUCKET: Bucket = Bucket::WitnessInput;
-    type Key<'a> = L1BatchNumber;
+    type Key<'a> = (L1BatchNumber, u64);
 
-    fn encode_key(key: Self::Key<'_>) -> String {
-        format!("run_with_fixed_params_input_{}.bin", key)
+    fn encode_key((block_number, geometry_hash): Self::Key<'_>) -> String {
+        format!(
+            "run_with_fixed_params_input_{}_{}.bin",
+            block_number, geometry_hash
+        )
     }
 
     zksync_object_store::serialize_using_bincode!();